@@ -0,0 +1,275 @@
+//! A flat, linear instruction program compiled from a [`Pattern`](crate::pattern::Pattern)'s
+//! fragment tree, plus the mutable odometer state needed to step through it.
+//!
+//! Rendering the currently selected candidate and bumping to the next one
+//! only ever walks this flat `Vec<Instr>` plus a separate `Vec<Counter>`
+//! state array, instead of recursing through the owned `Fragment`/`Switch`
+//! tree for every single candidate.
+
+/// A single instruction in a compiled pattern program.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instr {
+    /// Emit a literal string from the string pool.
+    EmitLiteral(usize),
+    /// Enter a switch. `option_offsets[i]` is the instruction index where
+    /// option `i`'s program starts; `after` is the instruction index where
+    /// execution resumes once the switch (whichever option is selected) is
+    /// done. The currently selected option is read from `counter_idx` in the
+    /// odometer state, so there is no separate "select option" instruction.
+    EnterSwitch {
+        counter_idx: usize,
+        option_offsets: Vec<usize>,
+        after: usize,
+    },
+    /// Marks the end of a single switch option's program. Used only as a
+    /// range boundary by [`option_bounds`] and never executed directly.
+    EndSwitch,
+}
+
+/// The current selection and radix of a single switch, addressed by index
+/// into a flat array instead of being owned by a tree node.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Counter {
+    pub ctr: usize,
+    pub len: usize,
+}
+
+/// A pattern compiled into a flat instruction program, ready to be stepped
+/// through without recursing into the `Fragment`/`Switch` tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    instrs: Vec<Instr>,
+    literals: Vec<String>,
+    counters: Vec<Counter>,
+    done: bool,
+}
+
+impl Chunk {
+    pub(crate) fn new(instrs: Vec<Instr>, literals: Vec<String>, counters: Vec<Counter>) -> Chunk {
+        Chunk {
+            instrs,
+            literals,
+            counters,
+            done: false,
+        }
+    }
+
+    /// Render the currently selected candidate into `out` and advance the
+    /// odometer to the next one.
+    #[inline]
+    pub fn next<'a>(&mut self, out: &'a mut String) -> Option<&'a mut String> {
+        if self.done {
+            return None;
+        }
+
+        let end = self.instrs.len();
+        render(&self.instrs, &self.literals, &self.counters, 0, end, out);
+        self.done = bump(&self.instrs, &mut self.counters, 0, end);
+        Some(out)
+    }
+
+    #[cfg(test)]
+    fn next_owned(&mut self) -> Option<String> {
+        let mut out = String::new();
+        self.next(&mut out)?;
+        Some(out)
+    }
+}
+
+/// The instruction range covered by option `idx`'s program, excluding its
+/// trailing `EndSwitch` marker.
+fn option_bounds(option_offsets: &[usize], after: usize, idx: usize) -> (usize, usize) {
+    let start = option_offsets[idx];
+    let content_end = option_offsets.get(idx + 1).copied().unwrap_or(after) - 1;
+    (start, content_end)
+}
+
+fn render(
+    instrs: &[Instr],
+    literals: &[String],
+    counters: &[Counter],
+    start: usize,
+    end: usize,
+    out: &mut String,
+) {
+    let mut pc = start;
+    while pc < end {
+        match &instrs[pc] {
+            Instr::EmitLiteral(id) => {
+                out.push_str(&literals[*id]);
+                pc += 1;
+            }
+            Instr::EnterSwitch {
+                counter_idx,
+                option_offsets,
+                after,
+            } => {
+                if !option_offsets.is_empty() {
+                    let cur = counters[*counter_idx].ctr;
+                    let (opt_start, opt_end) = option_bounds(option_offsets, *after, cur);
+                    render(instrs, literals, counters, opt_start, opt_end, out);
+                }
+                pc = *after;
+            }
+            Instr::EndSwitch => unreachable!("EndSwitch is only used as a range boundary marker"),
+        }
+    }
+}
+
+/// Advance the odometer over instructions `[start, end)`, the leftmost
+/// (first-compiled) switch being the fastest-changing digit, exactly
+/// mirroring `Pattern::bump`/`Switch::bump`'s carry semantics. Returns
+/// `true` once this range has wrapped all the way back to its initial
+/// state (i.e. every switch in it carried), `false` as soon as some switch
+/// advanced without needing to carry into its sibling.
+fn bump(instrs: &[Instr], counters: &mut [Counter], start: usize, end: usize) -> bool {
+    let mut pc = start;
+    while pc < end {
+        match &instrs[pc] {
+            Instr::EmitLiteral(_) => pc += 1,
+            Instr::EnterSwitch {
+                counter_idx,
+                option_offsets,
+                after,
+            } => {
+                let counter_idx = *counter_idx;
+                let after = *after;
+
+                if !option_offsets.is_empty() {
+                    let cur = counters[counter_idx].ctr;
+                    let (opt_start, opt_end) = option_bounds(option_offsets, after, cur);
+                    if !bump(instrs, counters, opt_start, opt_end) {
+                        return false;
+                    }
+                }
+
+                let counter = &mut counters[counter_idx];
+                counter.ctr += 1;
+                let wrapped = counter.ctr >= counter.len;
+                if wrapped {
+                    counter.ctr = 0;
+                }
+
+                pc = after;
+                if !wrapped {
+                    return false;
+                }
+            }
+            Instr::EndSwitch => unreachable!("EndSwitch is only used as a range boundary marker"),
+        }
+    }
+    true
+}
+
+/// Lower a parsed `Fragment` tree into a flat instruction program, interning
+/// literals into `literals` and allocating one `Counter` per switch.
+pub(crate) fn compile(
+    fragments: &[crate::pattern::Fragment],
+    instrs: &mut Vec<Instr>,
+    literals: &mut Vec<String>,
+    counters: &mut Vec<Counter>,
+) {
+    use crate::pattern::Fragment;
+
+    for frag in fragments {
+        match frag {
+            Fragment::Chunk(chunk) => {
+                let id = literals.len();
+                literals.push(chunk.clone());
+                instrs.push(Instr::EmitLiteral(id));
+            }
+            Fragment::Switch(switch) => {
+                let counter_idx = counters.len();
+                let options = switch.options();
+                counters.push(Counter {
+                    ctr: 0,
+                    len: options.len(),
+                });
+
+                let switch_idx = instrs.len();
+                instrs.push(Instr::EnterSwitch {
+                    counter_idx,
+                    option_offsets: Vec::new(),
+                    after: 0,
+                });
+
+                let mut option_offsets = Vec::with_capacity(options.len());
+                for option in options {
+                    option_offsets.push(instrs.len());
+                    compile(option, instrs, literals, counters);
+                    instrs.push(Instr::EndSwitch);
+                }
+
+                let after = instrs.len();
+                if let Instr::EnterSwitch {
+                    option_offsets: o,
+                    after: a,
+                    ..
+                } = &mut instrs[switch_idx]
+                {
+                    *o = option_offsets;
+                    *a = after;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::pattern::Pattern;
+    use std::str::FromStr;
+
+    fn all_via_chunk(pattern: &str) -> Vec<String> {
+        let pattern = Pattern::from_str(pattern).unwrap();
+        let mut chunk = pattern.compile();
+        let mut v = Vec::new();
+        while let Some(s) = chunk.next_owned() {
+            v.push(s);
+        }
+        v
+    }
+
+    fn all_via_tree(pattern: &str) -> Vec<String> {
+        let mut pattern = Pattern::from_str(pattern).unwrap();
+        let mut v = Vec::new();
+        while let Some(s) = pattern.next_owned() {
+            v.push(s);
+        }
+        v
+    }
+
+    fn assert_matches_tree(pattern: &str) {
+        assert_eq!(all_via_chunk(pattern), all_via_tree(pattern));
+    }
+
+    #[test]
+    fn simple() {
+        assert_matches_tree("abc");
+    }
+
+    #[test]
+    fn switch() {
+        assert_matches_tree("abc{x,y,z}");
+    }
+
+    #[test]
+    fn nested() {
+        assert_matches_tree("abc{x,{y,z}}");
+    }
+
+    #[test]
+    fn chained() {
+        assert_matches_tree("{x,y,z}{x,y,z}");
+    }
+
+    #[test]
+    fn empty_switch() {
+        assert_matches_tree("{}x{a,b}");
+    }
+
+    #[test]
+    fn deeply_nested() {
+        assert_matches_tree("{{{a,b,c},x},y}");
+    }
+}
@@ -0,0 +1,79 @@
+pub use anyhow::{anyhow, bail, Context, Error, Result};
+pub use log::debug;
+
+use std::fmt;
+use std::ops::Range;
+
+/// A parse error that points at an exact byte-offset span within the original
+/// pattern source, so a caret can be rendered underneath the offending text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    source: String,
+    span: Range<usize>,
+    message: String,
+    hints: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(source: impl Into<String>, span: Range<usize>, message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            source: source.into(),
+            span,
+            message: message.into(),
+            hints: Vec::new(),
+        }
+    }
+
+    /// Attach a non-fatal hint that is rendered below the caret.
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Diagnostic {
+        self.hints.push(hint.into());
+        self
+    }
+
+    /// Render the full `error: ...` / source / caret diagram, the way it's
+    /// meant to be shown to a human. `Display` only exposes the bare
+    /// message (for contexts like the REPL's inline validation hint, where a
+    /// multi-line diagram wouldn't fit), so call sites that report a parse
+    /// failure as the main event - the REPL's error echo, the CLI's pattern
+    /// argument parser - call this explicitly instead.
+    pub(crate) fn render(&self, colors: bool) -> String {
+        let start = self.span.start.min(self.source.len());
+        let end = self.span.end.clamp(start, self.source.len());
+        let width = (end - start).max(1);
+
+        let prefix = " ".repeat(self.source[..start].chars().count());
+        let caret = format!("^{}", "~".repeat(width.saturating_sub(1)));
+
+        let mut out = format!("error: {}\n{}\n", self.message, self.source);
+        if colors {
+            out.push_str(&format!("{}\x1b[1;31m{}\x1b[0m", prefix, caret));
+        } else {
+            out.push_str(&prefix);
+            out.push_str(&caret);
+        }
+
+        for hint in &self.hints {
+            out.push_str(&format!("\nhint: {}", hint));
+        }
+
+        out
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+/// Render `err` the way it should be shown as the main event of a failed
+/// parse: the full caret diagram if it's a [`Diagnostic`] (or wraps one),
+/// falling back to its plain `Display` message otherwise.
+pub(crate) fn report(err: &Error, colors: bool) -> String {
+    match err.downcast_ref::<Diagnostic>() {
+        Some(diagnostic) => diagnostic.render(colors),
+        None => format!("error: {}", err),
+    }
+}
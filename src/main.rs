@@ -1,5 +1,7 @@
+mod chunk;
 mod errors;
 mod pattern;
+mod repl;
 mod tokens;
 
 use crate::errors::*;
@@ -9,6 +11,11 @@ use env_logger::Env;
 use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
 use std::io::{self, IsTerminal, Write};
 use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::thread;
 use std::time::Duration;
 
 #[derive(Debug, Parser)]
@@ -25,7 +32,88 @@ pub struct Args {
     /// Send permutations to stdin of a subprocess
     #[arg(short = 'e', long = "exec")]
     exec: Option<String>,
-    pattern: Pattern,
+    /// Keep a single child process alive and stream candidates to its stdin
+    /// instead of spawning one process per candidate (requires --exec)
+    #[arg(long = "persistent", requires = "exec")]
+    persistent: bool,
+    /// Run this many subprocesses concurrently, each getting their own
+    /// candidate (requires --exec, conflicts with --persistent)
+    #[arg(short = 'j', long = "jobs", default_value_t = 1, requires = "exec", conflicts_with = "persistent")]
+    jobs: usize,
+    /// Skip ahead to the Nth permutation, so an interrupted run can be
+    /// resumed (conflicts with --jobs)
+    #[arg(long = "skip", default_value_t = 0, conflicts_with = "jobs")]
+    skip: usize,
+    /// Only emit permutations whose index falls into shard `k` of `m`, in
+    /// the form `k/m` (conflicts with --jobs)
+    #[arg(long = "shard", conflicts_with = "jobs")]
+    shard: Option<Shard>,
+    /// Start an interactive REPL to build and preview a pattern
+    #[arg(long = "repl")]
+    repl: bool,
+    /// Pattern to generate, e.g. `{a,b}{0..9}`. Note `\xNN` for NN > 0x7F
+    /// encodes as multi-byte UTF-8, not the raw byte `NN`
+    #[arg(required_unless_present = "repl", value_parser = parse_pattern)]
+    pattern: Option<Pattern>,
+}
+
+/// Parses the `pattern` positional argument, rendering the full caret
+/// diagram (rather than just the bare message clap would otherwise print
+/// via `Display`) when the pattern fails to parse.
+fn parse_pattern(s: &str) -> std::result::Result<Pattern, String> {
+    Pattern::from_str(s).map_err(|err| {
+        let colors = io::stderr().is_terminal();
+        report(&err, colors)
+    })
+}
+
+/// A `k/m` shard specifier for `--shard`: only permutations whose index
+/// satisfies `index % m == k` are emitted.
+#[derive(Debug, Clone, Copy)]
+struct Shard {
+    k: usize,
+    m: usize,
+}
+
+impl FromStr for Shard {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Shard> {
+        let (k, m) = s.split_once('/').context("shard must be in the form k/m")?;
+        let k: usize = k.parse().context("invalid shard index")?;
+        let m: usize = m.parse().context("invalid shard count")?;
+
+        if m == 0 {
+            bail!("shard count must be greater than zero");
+        }
+        if k >= m {
+            bail!("shard index must be less than shard count");
+        }
+
+        Ok(Shard { k, m })
+    }
+}
+
+/// Number of iterations `permutate_indexed` will actually perform for the
+/// given `skip`/`shard`, so the progress bar's total still matches reality
+/// (and reaches 100%) when only part of the keyspace is being covered.
+fn indexed_total(total: usize, skip: usize, shard: Option<Shard>) -> usize {
+    if skip >= total {
+        return 0;
+    }
+
+    match shard {
+        None => total - skip,
+        Some(shard) => {
+            let rem = skip % shard.m;
+            let first = skip + (shard.m + shard.k - rem) % shard.m;
+            if first >= total {
+                0
+            } else {
+                (total - first - 1) / shard.m + 1
+            }
+        }
+    }
 }
 
 pub enum SolveStatus<'a> {
@@ -34,7 +122,7 @@ pub enum SolveStatus<'a> {
     Unsolved,
 }
 
-trait Feedback {
+trait Feedback: Sync {
     fn found(&self, password: &[u8]);
 
     #[inline(always)]
@@ -105,7 +193,11 @@ impl Feedback for Verbose {
     }
 }
 
-trait Sink {
+/// A destination that a single candidate gets written to. `Send` so a sink
+/// can be handed to a worker thread, one per thread, in the `--jobs`
+/// parallel execution path instead of that path re-implementing spawn/write
+/// logic inline.
+trait Sink: Send {
     fn write<'a>(&mut self, b: &'a [u8]) -> Result<SolveStatus<'a>>;
 }
 
@@ -128,6 +220,7 @@ impl Sink for Stdout {
     }
 }
 
+#[derive(Clone)]
 struct Exec {
     bin: String,
     args: Vec<String>,
@@ -169,9 +262,158 @@ impl Sink for Exec {
     }
 }
 
-fn permutate(mut pattern: Pattern, sink: &mut dyn Sink, f: &dyn Feedback) -> Result<()> {
+/// A long-lived child process that candidates are streamed to, newline
+/// delimited, instead of spawning a fresh process per candidate. Much faster
+/// for tools that read guesses line-by-line, at the cost of being unable to
+/// tell which line a non-zero exit was caused by.
+struct Persistent {
+    child: std::process::Child,
+    stdin: Option<std::process::ChildStdin>,
+}
+
+impl Persistent {
+    fn new(cmd: &str) -> Result<Persistent> {
+        let exec = Exec::new(cmd)?;
+        let mut child = Command::new(&exec.bin)
+            .args(&exec.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .context("Failed to spawn child")?;
+        let stdin = child.stdin.take();
+        Ok(Persistent { child, stdin })
+    }
+}
+
+impl Sink for Persistent {
+    #[inline(always)]
+    fn write<'a>(&mut self, b: &'a [u8]) -> Result<SolveStatus<'a>> {
+        let Some(stdin) = self.stdin.as_mut() else {
+            return Ok(SolveStatus::UnknownSolution);
+        };
+
+        if stdin.write_all(b).is_err() {
+            // the child closed its stdin, most likely because it exited
+            Ok(SolveStatus::UnknownSolution)
+        } else {
+            Ok(SolveStatus::Unsolved)
+        }
+    }
+}
+
+impl Drop for Persistent {
+    fn drop(&mut self) {
+        // close our end of stdin so the child sees eof, then reap it
+        self.stdin = None;
+        let _ = self.child.wait();
+    }
+}
+
+fn permutate(pattern: Pattern, sink: &mut dyn Sink, f: &dyn Feedback) -> Result<()> {
+    let mut chunk = pattern.compile();
+    let mut out = String::new();
+    while let Some(out) = chunk.next(&mut out) {
+        out.push('\n');
+        match sink.write(out.as_bytes())? {
+            SolveStatus::KnownSolution(hit) => {
+                f.found(hit);
+                break;
+            }
+            SolveStatus::UnknownSolution => break,
+            SolveStatus::Unsolved => (),
+        }
+        out.clear();
+        f.inc();
+    }
+
+    f.finish();
+    Ok(())
+}
+
+/// Distribute candidates across `jobs` concurrent [`Sink`]s, each its own
+/// [`Exec`] so every worker spawns its own child the same way the
+/// single-threaded `--exec` path does. The candidate generator runs on this
+/// thread and feeds a bounded queue, so workers never run far ahead of it;
+/// the first child to exit successfully stops the whole pool and is reported
+/// as the [`SolveStatus::KnownSolution`], even though it may not be the last
+/// candidate that was generated. `f.inc()` fires once a worker's child has
+/// actually exited, not when a candidate is merely handed to the queue,
+/// keeping the progress-bar `Feedback` increments accurate across threads.
+fn permutate_parallel(pattern: Pattern, jobs: usize, cmd: &str, f: &dyn Feedback) -> Result<()> {
+    let exec = Exec::new(cmd)?;
+
+    let (work_tx, work_rx) = mpsc::sync_channel::<Vec<u8>>(jobs * 2);
+    let work_rx = Arc::new(Mutex::new(work_rx));
+    let (found_tx, found_rx) = mpsc::channel::<Vec<u8>>();
+    let stop = Arc::new(AtomicBool::new(false));
+
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            let work_rx = Arc::clone(&work_rx);
+            let found_tx = found_tx.clone();
+            let stop = Arc::clone(&stop);
+            let mut sink: Box<dyn Sink> = Box::new(exec.clone());
+            scope.spawn(move || loop {
+                let candidate = work_rx.lock().unwrap().recv();
+                let Ok(candidate) = candidate else {
+                    break;
+                };
+                if stop.load(Ordering::Relaxed) {
+                    continue;
+                }
+
+                if let Ok(SolveStatus::KnownSolution(hit)) = sink.write(&candidate) {
+                    stop.store(true, Ordering::Relaxed);
+                    let _ = found_tx.send(hit.to_vec());
+                }
+                f.inc();
+            });
+        }
+        drop(found_tx);
+
+        let mut chunk = pattern.compile();
+        let mut out = String::new();
+        while !stop.load(Ordering::Relaxed) {
+            let Some(candidate) = chunk.next(&mut out) else {
+                break;
+            };
+            candidate.push('\n');
+            if work_tx.send(candidate.clone().into_bytes()).is_err() {
+                break;
+            }
+            out.clear();
+        }
+        drop(work_tx);
+    });
+
+    if let Ok(hit) = found_rx.try_recv() {
+        f.found(&hit);
+    }
+
+    f.finish();
+    Ok(())
+}
+
+/// Generate candidates by direct index instead of stepping through the
+/// pattern one at a time, so a run can start partway through the keyspace
+/// (`skip`) and/or only cover a fraction of it (`shard`).
+fn permutate_indexed(
+    pattern: &Pattern,
+    skip: usize,
+    shard: Option<Shard>,
+    sink: &mut dyn Sink,
+    f: &dyn Feedback,
+) -> Result<()> {
+    let total = pattern.count();
     let mut out = String::new();
-    while let Some(out) = pattern.next(&mut out) {
+
+    for index in skip..total {
+        if shard.is_some_and(|shard| index % shard.m != shard.k) {
+            continue;
+        }
+
+        pattern.nth(index, &mut out);
         out.push('\n');
         match sink.write(out.as_bytes())? {
             SolveStatus::KnownSolution(hit) => {
@@ -200,25 +442,81 @@ fn main() -> Result<()> {
     };
     env_logger::init_from_env(Env::default().default_filter_or(log_level));
 
+    if args.repl {
+        return repl::run();
+    }
+
+    let pattern = args.pattern.unwrap();
+
     if args.count {
-        println!("{}", args.pattern.count());
+        println!("{}", pattern.count());
     } else {
-        let mut sink: Box<dyn Sink> = if let Some(exec) = args.exec {
-            Box::new(Exec::new(&exec)?)
-        } else {
-            Box::new(Stdout::new())
-        };
-
         let colors = io::stdout().is_terminal();
         let feedback: Box<dyn Feedback> = if args.quiet || !colors {
             Box::new(Silent { colors })
         } else {
-            let count = args.pattern.count();
+            let count = pattern.count();
+            let count = if args.skip > 0 || args.shard.is_some() {
+                indexed_total(count, args.skip, args.shard)
+            } else {
+                count
+            };
             Box::new(Verbose::new(count))
         };
 
-        permutate(args.pattern, &mut *sink, &*feedback)?;
+        if args.jobs > 1 {
+            let cmd = args.exec.as_deref().unwrap();
+            permutate_parallel(pattern, args.jobs, cmd, &*feedback)?;
+        } else {
+            let mut sink: Box<dyn Sink> = if let Some(exec) = &args.exec {
+                if args.persistent {
+                    Box::new(Persistent::new(exec)?)
+                } else {
+                    Box::new(Exec::new(exec)?)
+                }
+            } else {
+                Box::new(Stdout::new())
+            };
+
+            if args.skip > 0 || args.shard.is_some() {
+                permutate_indexed(&pattern, args.skip, args.shard, &mut *sink, &*feedback)?;
+            } else {
+                permutate(pattern, &mut *sink, &*feedback)?;
+            }
+        }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn indexed_total_no_skip_or_shard() {
+        assert_eq!(indexed_total(10, 0, None), 10);
+    }
+
+    #[test]
+    fn indexed_total_skip_only() {
+        assert_eq!(indexed_total(10, 3, None), 7);
+    }
+
+    #[test]
+    fn indexed_total_skip_past_end() {
+        assert_eq!(indexed_total(10, 10, None), 0);
+        assert_eq!(indexed_total(10, 20, None), 0);
+    }
+
+    #[test]
+    fn indexed_total_shard_only() {
+        assert_eq!(indexed_total(10, 0, Some(Shard { k: 0, m: 2 })), 5);
+        assert_eq!(indexed_total(10, 0, Some(Shard { k: 1, m: 2 })), 5);
+    }
+
+    #[test]
+    fn indexed_total_skip_and_shard() {
+        assert_eq!(indexed_total(10, 4, Some(Shard { k: 0, m: 3 })), 2);
+    }
+}
@@ -1,5 +1,6 @@
+use crate::chunk::{self, Chunk};
 use crate::errors::*;
-use crate::tokens::{self, Token};
+use crate::tokens::{self, Node};
 use std::str::FromStr;
 
 #[derive(Debug, PartialEq)]
@@ -58,17 +59,66 @@ impl Pattern {
     }
 
     pub fn count(&self) -> usize {
-        let mut sum = 1;
-
-        for frag in &self.fragments {
-            let m = match frag {
-                Fragment::Chunk(_) => 1,
-                Fragment::Switch(switch) => switch.count(),
-            };
-            sum *= m;
-        }
+        fragments_count(&self.fragments)
+    }
+
+    /// Lower this pattern's fragment tree into a flat instruction [`Chunk`],
+    /// so that generating candidates no longer has to recurse through the
+    /// tree for every single one.
+    pub fn compile(&self) -> Chunk {
+        let mut instrs = Vec::new();
+        let mut literals = Vec::new();
+        let mut counters = Vec::new();
+        chunk::compile(&self.fragments, &mut instrs, &mut literals, &mut counters);
+        Chunk::new(instrs, literals, counters)
+    }
+
+    /// Render the `index`-th candidate directly into `out`, without
+    /// stepping through every candidate before it.
+    ///
+    /// `index` is decomposed as a mixed-radix number across the fragment
+    /// list, the leftmost switch being the least significant digit, exactly
+    /// mirroring the order [`Pattern::next`]/[`Pattern::bump`] iterate in:
+    /// `pattern.nth(i, &mut out)` produces the same string as the `i`-th
+    /// call to [`Pattern::next`]. `index` must be less than [`Pattern::count`].
+    pub fn nth(&self, index: usize, out: &mut String) {
+        render_nth(&self.fragments, index, out);
+    }
+}
+
+/// The number of candidates `fragments` alone can produce: the product of
+/// each fragment's contribution (a bare chunk always contributes `1`). A
+/// switch with no options (e.g. a literal `{}`) contributes a count of `0`,
+/// but - mirroring `render_nth`'s "consumes no digit" treatment of it - that
+/// shouldn't zero out the whole product, so such switches are skipped here
+/// rather than multiplied in.
+fn fragments_count(fragments: &[Fragment]) -> usize {
+    fragments
+        .iter()
+        .map(|frag| match frag {
+            Fragment::Chunk(_) => 1,
+            Fragment::Switch(switch) => switch.count(),
+        })
+        .filter(|&count| count != 0)
+        .product()
+}
 
-        sum
+/// Render the candidate at `index` within `fragments` into `out`, consuming
+/// `index` one switch at a time (least significant, i.e. leftmost, first).
+fn render_nth(fragments: &[Fragment], mut index: usize, out: &mut String) {
+    for frag in fragments {
+        match frag {
+            Fragment::Chunk(chunk) => out.push_str(chunk),
+            Fragment::Switch(switch) => {
+                let count = switch.count();
+                if count == 0 {
+                    // nothing to choose from; consumes no digit
+                    continue;
+                }
+                switch.render_nth(index % count, out);
+                index /= count;
+            }
+        }
     }
 }
 
@@ -85,45 +135,38 @@ impl From<Vec<Fragment>> for Pattern {
 impl FromStr for Pattern {
     type Err = Error;
 
-    // TODO: this is executed twice(?!)
     fn from_str(s: &str) -> Result<Pattern> {
-        let tokens = tokens::parse(s)?;
-        debug!("parsed into tokens: {:?}", tokens);
-
-        let mut switches: Vec<Switch> = Vec::new();
-        let mut fragments = Vec::new();
-
-        for token in tokens {
-            debug!("adding token: {:?}", token);
-            match token {
-                Token::Chunk(chunk) => {
-                    if let Some(tail) = switches.last_mut() {
-                        tail.push(Fragment::Chunk(chunk));
-                    } else {
-                        fragments.push(Fragment::Chunk(chunk));
-                    }
-                }
-                Token::SwitchOpen => {
-                    switches.push(Switch::new());
-                }
-                Token::SwitchClose => {
-                    let mut switch = switches.pop().unwrap();
-                    switch.reset();
+        let nodes = tokens::parse(s)?;
+        debug!("parsed into nodes: {:?}", nodes);
+        Ok(Pattern::from(fragments_from_nodes(nodes)))
+    }
+}
 
-                    if let Some(tail) = switches.last_mut() {
-                        tail.push(Fragment::Switch(switch));
+/// Lower a parsed [`Node`] tree into the [`Fragment`]/[`Switch`] shape the
+/// generator works with; this is a direct structural translation, since
+/// [`tokens::parse`] already resolved the nesting, except that an empty
+/// switch alternative (e.g. the last one in `{a,}`) becomes a single empty
+/// [`Fragment::Chunk`].
+fn fragments_from_nodes(nodes: Vec<Node>) -> Vec<Fragment> {
+    nodes.into_iter().map(fragment_from_node).collect()
+}
+
+fn fragment_from_node(node: Node) -> Fragment {
+    match node {
+        Node::Literal(chunk) => Fragment::Chunk(chunk),
+        Node::Switch(options) => {
+            let options = options
+                .into_iter()
+                .map(|option| {
+                    if option.is_empty() {
+                        vec![Fragment::Chunk(String::new())]
                     } else {
-                        fragments.push(Fragment::Switch(switch));
+                        fragments_from_nodes(option)
                     }
-                }
-                Token::SwitchNext => {
-                    let tail = switches.last_mut().unwrap();
-                    tail.bump_write_cursor();
-                }
-            }
+                })
+                .collect();
+            Fragment::Switch(Switch::from(options))
         }
-
-        Ok(Pattern::from(fragments))
     }
 }
 
@@ -137,37 +180,12 @@ pub enum Fragment {
 pub struct Switch {
     options: Vec<Vec<Fragment>>,
     ctr: usize,
-    write_cursor: usize,
 }
 
 impl Switch {
-    #[inline]
-    pub fn new() -> Switch {
-        Switch {
-            options: Vec::new(),
-            ctr: 0,
-            write_cursor: 0,
-        }
-    }
-
-    #[inline]
-    pub fn push(&mut self, frag: Fragment) {
-        if self.write_cursor + 1 > self.options.len() {
-            self.options.push(Vec::new());
-        }
-
-        let tail = &mut self.options[self.write_cursor];
-        tail.push(frag);
-    }
-
-    #[inline(always)]
-    pub fn bump_write_cursor(&mut self) {
-        self.write_cursor += 1;
-    }
-
-    #[inline(always)]
-    pub fn reset(&mut self) {
-        self.write_cursor = 0;
+    /// The alternatives this switch can expand to, in option order.
+    pub(crate) fn options(&self) -> &[Vec<Fragment>] {
+        &self.options
     }
 
     #[inline]
@@ -210,33 +228,29 @@ impl Switch {
     }
 
     pub fn count(&self) -> usize {
-        let mut sum = 0;
-
-        for fragments in &self.options {
-            let mut sum2 = 1;
-
-            for frag in fragments {
-                let m = match frag {
-                    Fragment::Chunk(_) => 1,
-                    Fragment::Switch(switch) => switch.count(),
-                };
-                sum2 *= m;
+        self.options.iter().map(|option| fragments_count(option)).sum()
+    }
+
+    /// Render the option selected by `index` into `out`, where `index` is
+    /// in `[0, self.count())`. Options are tried in order, each consuming
+    /// as many indices as it can produce, mirroring how [`Switch::bump`]
+    /// only advances `ctr` once the currently selected option has cycled
+    /// through all of its own combinations.
+    fn render_nth(&self, mut index: usize, out: &mut String) {
+        for option in &self.options {
+            let count = fragments_count(option);
+            if index < count {
+                render_nth(option, index, out);
+                return;
             }
-
-            sum += sum2;
+            index -= count;
         }
-
-        sum
     }
 }
 
 impl From<Vec<Vec<Fragment>>> for Switch {
     fn from(options: Vec<Vec<Fragment>>) -> Switch {
-        Switch {
-            options,
-            ctr: 0,
-            write_cursor: 0,
-        }
+        Switch { options, ctr: 0 }
     }
 }
 
@@ -457,7 +471,7 @@ mod test {
         let p = Pattern::from_str("{}").unwrap();
         let p2 = Pattern::from(vec![Fragment::Switch(Switch::from(vec![]))]);
         assert_eq!(p, p2);
-        // assert_eq!(p.count(), all(p).len());
+        assert_eq!(p.count(), all(p).len());
         assert_eq!(all(p2), vec![String::new()]);
     }
 
@@ -592,10 +606,230 @@ mod test {
         assert!(Pattern::from_str("{..0}").is_err());
         assert!(Pattern::from_str("{00..}").is_err());
         assert!(Pattern::from_str("{..00}").is_err());
-        assert!(Pattern::from_str("{00..00}").is_err());
         assert!(Pattern::from_str("{.").is_err());
         assert!(Pattern::from_str("{..").is_err());
         assert!(Pattern::from_str("{...}").is_err());
         assert!(Pattern::from_str("{a...}").is_err());
     }
+
+    #[test]
+    fn alphabetic_range() {
+        let p = Pattern::from_str("{a..e}").unwrap();
+        assert_eq!(p.count(), 5);
+        assert_eq!(
+            all(p),
+            vec![
+                String::from("a"),
+                String::from("b"),
+                String::from("c"),
+                String::from("d"),
+                String::from("e"),
+            ]
+        );
+    }
+
+    #[test]
+    fn multi_digit_range() {
+        let p = Pattern::from_str("{8..11}").unwrap();
+        assert_eq!(p.count(), 4);
+        assert_eq!(
+            all(p),
+            vec![
+                String::from("8"),
+                String::from("9"),
+                String::from("10"),
+                String::from("11"),
+            ]
+        );
+    }
+
+    #[test]
+    fn descending_range() {
+        let p = Pattern::from_str("{3..0}").unwrap();
+        assert_eq!(p.count(), 4);
+        assert_eq!(
+            all(p),
+            vec![
+                String::from("3"),
+                String::from("2"),
+                String::from("1"),
+                String::from("0"),
+            ]
+        );
+    }
+
+    #[test]
+    fn stepped_range() {
+        let p = Pattern::from_str("{0..10..5}").unwrap();
+        assert_eq!(p.count(), 3);
+        assert_eq!(
+            all(p),
+            vec![String::from("0"), String::from("5"), String::from("10"),]
+        );
+    }
+
+    #[test]
+    fn zero_padded_range() {
+        let p = Pattern::from_str("{01..12}").unwrap();
+        assert_eq!(p.count(), 12);
+        assert_eq!(
+            all(p),
+            vec![
+                "01", "02", "03", "04", "05", "06", "07", "08", "09", "10", "11", "12",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn zero_padded_range_width() {
+        let p = Pattern::from_str("{001..999}").unwrap();
+        assert_eq!(p.count(), 999);
+    }
+
+    // These two cover larger/multi-component numeric ranges; the parsing
+    // itself was already implemented by `expand_range` (see chunk0-3), so
+    // they're regression coverage rather than new range-handling behavior.
+    #[test]
+    fn large_integer_range() {
+        let p = Pattern::from_str("{1..100}").unwrap();
+        assert_eq!(p.count(), 100);
+        let values = all(p);
+        assert_eq!(values.first(), Some(&String::from("1")));
+        assert_eq!(values.last(), Some(&String::from("100")));
+    }
+
+    #[test]
+    fn ip_octet_ranges() {
+        let p = Pattern::from_str("{0..255}.{0..255}.{0..255}.{0..255}").unwrap();
+        assert_eq!(p.count(), 256 * 256 * 256 * 256);
+    }
+
+    #[test]
+    fn degenerate_zero_padded_range() {
+        let p = Pattern::from_str("{00..00}").unwrap();
+        assert_eq!(all(p), vec![String::from("00")]);
+    }
+
+    fn assert_nth_matches_next(pattern: &str) {
+        let p = Pattern::from_str(pattern).unwrap();
+        let expected = all(Pattern::from_str(pattern).unwrap());
+
+        let mut out = String::new();
+        for (i, expected) in expected.iter().enumerate() {
+            out.clear();
+            p.nth(i, &mut out);
+            assert_eq!(&out, expected);
+        }
+    }
+
+    #[test]
+    fn nth_simple() {
+        assert_nth_matches_next("abc{x,y,z}");
+    }
+
+    #[test]
+    fn nth_nested() {
+        assert_nth_matches_next("a{b,c{x,y},d}");
+    }
+
+    #[test]
+    fn nth_chained() {
+        assert_nth_matches_next("{x,y,z}{x,y,z}");
+    }
+
+    #[test]
+    fn nth_empty_switch() {
+        let p = Pattern::from_str("{}x{a,b}").unwrap();
+        let mut out = String::new();
+        p.nth(0, &mut out);
+        assert_eq!(out, "xa");
+        out.clear();
+        p.nth(1, &mut out);
+        assert_eq!(out, "xb");
+    }
+
+    /// `permutate_indexed`/`indexed_total` (in `main.rs`) iterate
+    /// `0..pattern.count()` and call `pattern.nth()` directly, so
+    /// `count()` must agree with how many candidates `nth` can actually
+    /// produce for a pattern containing an empty switch - unlike
+    /// `nth_empty_switch` above, which hardcodes the indices and so
+    /// wouldn't catch `count()` itself being wrong.
+    #[test]
+    fn count_matches_nth_with_empty_switch() {
+        let p = Pattern::from_str("{}x{a,b}").unwrap();
+        assert_eq!(p.count(), 2);
+
+        let mut out = String::new();
+        let candidates: Vec<String> = (0..p.count())
+            .map(|index| {
+                out.clear();
+                p.nth(index, &mut out);
+                out.clone()
+            })
+            .collect();
+        assert_eq!(candidates, vec![String::from("xa"), String::from("xb")]);
+    }
+
+    #[test]
+    fn character_class() {
+        let p = Pattern::from_str("[a-c0-1]").unwrap();
+        assert_eq!(p.count(), 5);
+        assert_eq!(
+            all(p),
+            vec!["a", "b", "c", "0", "1"]
+                .into_iter()
+                .map(String::from)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn character_class_in_context() {
+        let p = Pattern::from_str("user[0-2]@example.com").unwrap();
+        assert_eq!(
+            all(p),
+            vec![
+                "user0@example.com",
+                "user1@example.com",
+                "user2@example.com",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn bounded_repetition() {
+        let p = Pattern::from_str("{a,b}{2,3}").unwrap();
+        // lengths 2 (2^2=4 combos) and 3 (2^3=8 combos)
+        assert_eq!(p.count(), 4 + 8);
+        assert_eq!(p.count(), all(p).len());
+    }
+
+    #[test]
+    fn exact_repetition() {
+        let p = Pattern::from_str("[0-1]{3}").unwrap();
+        assert_eq!(p.count(), 8);
+        assert_eq!(
+            all(p),
+            vec![
+                "000", "100", "010", "110", "001", "101", "011", "111",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn repeated_switch_followed_by_another_switch() {
+        // `{x,y,z}{x,y,z}` stays two independent switches (cartesian
+        // product), since its content isn't a bare quantifier.
+        let p = Pattern::from_str("{x,y,z}{x,y,z}").unwrap();
+        assert_eq!(p.count(), 9);
+    }
 }
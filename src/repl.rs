@@ -0,0 +1,148 @@
+use crate::errors::*;
+use crate::pattern::Pattern;
+use crate::tokens;
+use rustyline::completion::{Completer, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Context, Editor, Helper};
+use std::borrow::Cow;
+use std::io::{self, IsTerminal};
+use std::str::FromStr;
+
+/// Number of sample permutations printed after a pattern is accepted.
+const SAMPLE_SIZE: usize = 10;
+
+/// Returns true if `s` still has an unclosed `{`, ignoring escaped braces.
+fn has_unbalanced_open_brace(s: &str) -> bool {
+    let mut depth: i64 = 0;
+    let mut escape = false;
+    for c in s.chars() {
+        if escape {
+            escape = false;
+            continue;
+        }
+        match c {
+            '\\' => escape = true,
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            _ => (),
+        }
+    }
+    depth > 0
+}
+
+/// A `rustyline` helper that understands progpick's pattern syntax.
+pub struct PatternHelper;
+
+impl Validator for PatternHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        let input = ctx.input();
+        if input.is_empty() {
+            return Ok(ValidationResult::Valid(None));
+        }
+
+        match tokens::parse(input) {
+            Ok(_) => Ok(ValidationResult::Valid(None)),
+            Err(_) if has_unbalanced_open_brace(input) => Ok(ValidationResult::Incomplete),
+            Err(err) => Ok(ValidationResult::Invalid(Some(format!(" - {}", err)))),
+        }
+    }
+}
+
+impl Highlighter for PatternHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        let mut out = String::with_capacity(line.len());
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' | '}' | ',' => out.push_str(&format!("\x1b[1;33m{}\x1b[0m", c)),
+                '.' if chars.peek() == Some(&'.') => {
+                    chars.next();
+                    out.push_str("\x1b[1;36m..\x1b[0m");
+                }
+                c => out.push(c),
+            }
+        }
+        Cow::Owned(out)
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize, _forced: bool) -> bool {
+        true
+    }
+}
+
+impl Hinter for PatternHelper {
+    type Hint = String;
+
+    fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<String> {
+        if pos != line.len() || line.is_empty() {
+            return None;
+        }
+
+        let pattern = Pattern::from_str(line).ok()?;
+        Some(format!("\x1b[2m ({} permutations)\x1b[0m", pattern.count()))
+    }
+}
+
+impl Completer for PatternHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        _line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        // The pattern grammar has no fixed vocabulary to complete against.
+        Ok((pos, Vec::new()))
+    }
+}
+
+impl Helper for PatternHelper {}
+
+/// Run the interactive pattern-building REPL.
+pub fn run() -> Result<()> {
+    let mut rl: Editor<PatternHelper, rustyline::history::DefaultHistory> = Editor::new()?;
+    rl.set_helper(Some(PatternHelper));
+
+    println!("progpick repl - type a pattern, press enter to preview it, Ctrl-D to quit");
+
+    loop {
+        match rl.readline(">> ") {
+            Ok(line) => {
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+
+                let _ = rl.add_history_entry(line);
+
+                match Pattern::from_str(line) {
+                    Ok(mut pattern) => {
+                        let total = pattern.count();
+                        println!("total permutations: {}", total);
+
+                        let mut out = String::new();
+                        for _ in 0..SAMPLE_SIZE {
+                            let Some(sample) = pattern.next(&mut out) else {
+                                break;
+                            };
+                            println!("  {}", sample);
+                            out.clear();
+                        }
+                    }
+                    Err(err) => {
+                        let colors = io::stderr().is_terminal();
+                        eprintln!("{}", report(&err, colors));
+                    }
+                }
+            }
+            Err(rustyline::error::ReadlineError::Interrupted) => continue,
+            Err(rustyline::error::ReadlineError::Eof) => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    Ok(())
+}
@@ -1,138 +1,604 @@
-use crate::errors::*;
-
-#[derive(Debug, PartialEq)]
-pub enum Token {
-    Chunk(String),
-    SwitchOpen,
-    SwitchClose,
-    SwitchNext,
+use crate::errors::{Diagnostic, Result};
+use std::iter::Peekable;
+use std::ops::Range;
+use std::str::CharIndices;
+
+type Chars<'a> = Peekable<CharIndices<'a>>;
+
+/// A parsed pattern fragment. A [`Node::Switch`] holds its alternatives
+/// directly, each alternative itself being a sequence of `Node`s, so the
+/// nesting of `{}` in the source is represented structurally instead of
+/// being re-derived from a flat token stream.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Literal(String),
+    Switch(Vec<Vec<Node>>),
 }
 
-// TODO: refactor to iterator
-// TODO: custom bail macro that points at error position: ~~~^
-pub fn parse(s: &str) -> Result<Vec<Token>> {
-    let mut tokens = Vec::new();
+/// Returns `Some(c)` if `s` is exactly one ascii letter.
+fn single_ascii_alpha(s: &str) -> Option<char> {
+    let mut chars = s.chars();
+    let c = chars.next()?;
+    if chars.next().is_none() && c.is_ascii_alphabetic() {
+        Some(c)
+    } else {
+        None
+    }
+}
 
-    let mut in_switch: u8 = 0;
-    let mut escape = false;
+/// Upper bound on how many values a single `{start..end}` range is allowed to
+/// expand to. Each value becomes its own [`Node`] alternative, so an
+/// unbounded range (e.g. `{0..999999999}`) would otherwise try to allocate
+/// hundreds of millions of `String`s and `Node`s up front, before a single
+/// candidate is generated.
+const MAX_RANGE_LEN: u64 = 1_000_000;
 
-    let mut x = String::new();
-    let mut iter = s.chars().peekable();
-    while let Some(c) = iter.next() {
-        match c {
-            c if escape => {
-                x.push(c);
-                escape = false;
+/// Expand a `{start..end}` or `{start..end..step}` range into its literal options.
+///
+/// Tries, in order: a decimal integer range (supporting zero-padding and an
+/// optional step), a single-letter alphabetic range, and finally the original
+/// single ascii byte range for anything else.
+fn expand_range(
+    source: &str,
+    span: Range<usize>,
+    start: &str,
+    end: &str,
+    step: Option<&str>,
+) -> Result<Vec<String>> {
+    fn parse_step(source: &str, span: &Range<usize>, step: Option<&str>) -> Result<i64> {
+        let step = match step {
+            Some(step) => step
+                .parse::<i64>()
+                .map_err(|_| Diagnostic::new(source, span.clone(), "range step must be an integer"))?,
+            None => 1,
+        };
+        if step == 0 {
+            return Err(Diagnostic::new(source, span.clone(), "range step can't be 0").into());
+        }
+        Ok(step.abs())
+    }
+
+    if let (Ok(start_n), Ok(end_n)) = (start.parse::<i64>(), end.parse::<i64>()) {
+        let pad = (start.len() > 1 && start.starts_with('0')) || (end.len() > 1 && end.starts_with('0'));
+        let width = start.len().max(end.len());
+        let step_n = parse_step(source, &span, step)?;
+
+        let len = start_n.abs_diff(end_n) / step_n.unsigned_abs() + 1;
+        if len > MAX_RANGE_LEN {
+            return Err(Diagnostic::new(
+                source,
+                span,
+                format!(
+                    "range would expand to {len} values, which exceeds the limit of {MAX_RANGE_LEN}; narrow the range or use a larger step",
+                ),
+            )
+            .into());
+        }
+
+        let fmt = |v: i64| -> String {
+            if pad {
+                format!("{:0width$}", v, width = width)
+            } else {
+                v.to_string()
             }
-            '{' => {
-                if !x.is_empty() {
-                    tokens.push(Token::Chunk(x));
-                    x = String::new();
+        };
+
+        let mut values = Vec::new();
+        if start_n <= end_n {
+            let mut v = start_n;
+            while v <= end_n {
+                values.push(fmt(v));
+                v += step_n;
+            }
+        } else {
+            let mut v = start_n;
+            while v >= end_n {
+                values.push(fmt(v));
+                v -= step_n;
+            }
+        }
+        return Ok(values);
+    }
+
+    if let (Some(start_c), Some(end_c)) = (single_ascii_alpha(start), single_ascii_alpha(end)) {
+        if start_c.is_ascii_lowercase() != end_c.is_ascii_lowercase() {
+            return Err(Diagnostic::new(
+                source,
+                span,
+                "alphabetic ranges can't mix upper and lower case, e.g. use `{a..z}` or `{A..Z}`",
+            )
+            .into());
+        }
+
+        let step_n = parse_step(source, &span, step)? as u8;
+        let (lo, hi) = (start_c as u8, end_c as u8);
+
+        let mut values = Vec::new();
+        if lo <= hi {
+            let mut v = lo;
+            loop {
+                values.push((v as char).to_string());
+                match v.checked_add(step_n) {
+                    Some(next) if next <= hi => v = next,
+                    _ => break,
                 }
-                tokens.push(Token::SwitchOpen);
-                in_switch += 1;
             }
-            '}' => {
-                if in_switch == 0 {
-                    bail!("unmatched }}, not in a switch statement");
+        } else {
+            let mut v = lo;
+            loop {
+                values.push((v as char).to_string());
+                match v.checked_sub(step_n) {
+                    Some(next) if next >= hi => v = next,
+                    _ => break,
                 }
+            }
+        }
+        return Ok(values);
+    }
 
-                if !x.is_empty() {
-                    tokens.push(Token::Chunk(x));
-                    x = String::new();
-                }
-                tokens.push(Token::SwitchClose);
-                in_switch -= 1;
+    if step.is_none() && start.len() == 1 && end.len() == 1 {
+        let lo = start.as_bytes()[0];
+        let hi = end.as_bytes()[0];
+        if lo >= hi {
+            return Err(Diagnostic::new(source, span, "start needs to be smaller than end").into());
+        }
+        return Ok((lo..=hi).map(|b| (b as char).to_string()).collect());
+    }
 
-                // make sure the next ',' doesn't consider this an explicit empty option
-                if iter.peek() == Some(&',') {
-                    iter.next();
-                    tokens.push(Token::SwitchNext);
+    Err(Diagnostic::new(
+        source,
+        span,
+        "range patterns only support a single ascii character, an alphabetic range (a..z) or a numeric range (0..9)",
+    )
+    .into())
+}
+
+/// Decode a single escape sequence right after a `\` encountered at
+/// `backslash_pos`, consuming however many source characters it needs.
+///
+/// Recognizes `\n \r \t \0 \\`, two-digit hex `\xNN`, and braced Unicode
+/// `\u{1F600}`; any other escaped character (including pattern
+/// metacharacters like `\{ \} \, \.`) passes through unchanged, which is
+/// what lets those be written literally.
+///
+/// Caveat: since candidates are built up as a `String`, which must be valid
+/// UTF-8, `\xNN` can only round-trip to the single raw byte `NN` when
+/// `NN <= 0x7F`. For `NN >= 0x80` it decodes to the Unicode scalar `U+00NN`
+/// instead, which is then UTF-8 encoded into the output as *two* bytes -
+/// not the single raw byte a wordlist author asking for `\xFF` would expect.
+fn decode_escape(s: &str, iter: &mut Chars, backslash_pos: usize) -> Result<char> {
+    let (i, c) = iter.next().ok_or_else(|| {
+        Diagnostic::new(
+            s,
+            backslash_pos..s.len(),
+            "unexpected end of string in escape sequence",
+        )
+    })?;
 
-                    // if this is an explicit empty option add empty string
-                    if iter.peek() == Some(&'}') {
-                        tokens.push(Token::Chunk(String::new()));
+    match c {
+        'n' => Ok('\n'),
+        'r' => Ok('\r'),
+        't' => Ok('\t'),
+        '0' => Ok('\0'),
+        'x' => {
+            let mut hex = String::new();
+            for _ in 0..2 {
+                match iter.next() {
+                    Some((_, h)) if h.is_ascii_hexdigit() => hex.push(h),
+                    _ => {
+                        return Err(Diagnostic::new(
+                            s,
+                            backslash_pos..i + 1,
+                            "`\\x` escape requires exactly two hex digits",
+                        )
+                        .into());
                     }
                 }
             }
-            ',' if in_switch > 0 => {
-                tokens.push(Token::Chunk(x));
-                tokens.push(Token::SwitchNext);
-
-                // in case of an explicit last ',', push an empty chunk
-                if iter.peek() == Some(&'}') {
-                    tokens.push(Token::Chunk(String::new()));
+            let byte = u8::from_str_radix(&hex, 16).unwrap();
+            Ok(byte as char)
+        }
+        'u' => {
+            match iter.next() {
+                Some((_, '{')) => (),
+                _ => {
+                    return Err(Diagnostic::new(
+                        s,
+                        backslash_pos..i + 1,
+                        "`\\u` escape must be followed by `{`",
+                    )
+                    .into());
                 }
-
-                x = String::new();
             }
-            '.' if in_switch > 0 => {
-                if iter.peek() == Some(&'.') {
-                    iter.next(); // consume the value
 
-                    // ensure start is a single byte
-                    if x.as_bytes().len() != 1 {
-                        bail!("range patterns only support a single ascii character");
+            let mut hex = String::new();
+            let end = loop {
+                match iter.next() {
+                    Some((j, '}')) => break j + 1,
+                    Some((_, h)) if h.is_ascii_hexdigit() => hex.push(h),
+                    Some((j, _)) => {
+                        return Err(Diagnostic::new(
+                            s,
+                            backslash_pos..j,
+                            "`\\u{...}` escape contains a non-hex digit",
+                        )
+                        .into());
                     }
+                    None => {
+                        return Err(Diagnostic::new(
+                            s,
+                            backslash_pos..s.len(),
+                            "unterminated `\\u{...}` escape, expected `}`",
+                        )
+                        .into());
+                    }
+                }
+            };
 
-                    let start = x.chars().next().unwrap();
+            if hex.is_empty() || hex.len() > 6 {
+                return Err(Diagnostic::new(
+                    s,
+                    backslash_pos..end,
+                    "`\\u{...}` escape must contain 1 to 6 hex digits",
+                )
+                .into());
+            }
 
-                    // test for escape sequence
-                    let mut end = iter
-                        .next()
-                        .context("unexpected end of string in range pattern")?;
+            let code_point = u32::from_str_radix(&hex, 16).unwrap();
+            char::from_u32(code_point).ok_or_else(|| {
+                Diagnostic::new(
+                    s,
+                    backslash_pos..end,
+                    "`\\u{...}` escape is not a valid Unicode scalar value",
+                )
+                .into()
+            })
+        }
+        c => Ok(c),
+    }
+}
 
-                    if end == '\\' {
-                        end = iter
-                            .next()
-                            .context("unexpected end of string in escape sequence")?;
-                    }
+/// Parse a `[a-z0-9_]`-style character class, consuming up to its closing
+/// `]`, lowering to the same `Switch` alternatives a `{a,b,c}` would produce:
+/// one alternative per listed character, and one per character in an
+/// ascending `lo-hi` range.
+fn parse_class(s: &str, iter: &mut Chars, open_pos: usize) -> Result<Node> {
+    let mut options: Vec<Vec<Node>> = Vec::new();
 
-                    // ensure end is also a single byte
-                    if end.len_utf8() != 1 {
-                        bail!("range patterns only support a single ascii character");
-                    }
+    loop {
+        let (i, c) = match iter.next() {
+            Some(pair) => pair,
+            None => {
+                return Err(Diagnostic::new(
+                    s,
+                    open_pos..open_pos + 1,
+                    "unmatched `[`, still in character class at end of string",
+                )
+                .into());
+            }
+        };
 
-                    if iter.peek() != Some(&'}') {
-                        bail!("range patterns only support a single ascii character");
-                    }
+        match c {
+            '\\' => {
+                let decoded = decode_escape(s, iter, i)?;
+                options.push(vec![Node::Literal(decoded.to_string())]);
+            }
+            ']' => return Ok(Node::Switch(options)),
+            lo if iter.peek().map(|(_, c)| *c) == Some('-') => {
+                let mut lookahead = iter.clone();
+                lookahead.next(); // the '-'
 
-                    // expand range
-                    let start = start as u8;
-                    let end = end as u8;
+                match lookahead.peek().copied() {
+                    // a trailing `-` right before `]` is just a literal `-`
+                    None | Some((_, ']')) => options.push(vec![Node::Literal(lo.to_string())]),
+                    Some((j, hi)) => {
+                        iter.next(); // consume '-'
+                        iter.next(); // consume `hi`
 
-                    if start >= end {
-                        bail!("start needs to be smaller than end");
-                    }
+                        if !lo.is_ascii() || !hi.is_ascii() || lo > hi {
+                            return Err(Diagnostic::new(
+                                s,
+                                i..j + hi.len_utf8(),
+                                "character class ranges must be ascending ascii, e.g. `a-z`",
+                            )
+                            .into());
+                        }
 
-                    for c in start..=end {
-                        tokens.push(Token::Chunk((c as char).to_string()));
-                        if c < end {
-                            tokens.push(Token::SwitchNext);
+                        for b in (lo as u8)..=(hi as u8) {
+                            options.push(vec![Node::Literal((b as char).to_string())]);
                         }
                     }
+                }
+            }
+            c => options.push(vec![Node::Literal(c.to_string())]),
+        }
+    }
+}
 
-                    x = String::new();
-                } else {
-                    x.push(c);
+/// If `iter` is positioned right at a bare `{m}` or `{m,n}` quantifier —
+/// nothing else, no nested groups or ranges — consume it and return its
+/// `(min, max)` bounds. Otherwise `iter` is left untouched, so the caller
+/// falls back to parsing an ordinary adjacent switch (this is what keeps
+/// `{x,y,z}{x,y,z}` as two independent switches instead of a quantifier).
+fn try_parse_repeat(iter: &mut Chars) -> Option<(usize, usize)> {
+    let mut lookahead = iter.clone();
+    if lookahead.next().map(|(_, c)| c) != Some('{') {
+        return None;
+    }
+
+    fn read_digits(iter: &mut Chars) -> String {
+        let mut digits = String::new();
+        while let Some(c) = iter.peek().map(|(_, c)| *c) {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            digits.push(c);
+            iter.next();
+        }
+        digits
+    }
+
+    let min_digits = read_digits(&mut lookahead);
+    if min_digits.is_empty() {
+        return None;
+    }
+
+    let (min, max) = match lookahead.peek().map(|(_, c)| *c) {
+        Some('}') => {
+            lookahead.next();
+            let n = min_digits.parse().ok()?;
+            (n, n)
+        }
+        Some(',') => {
+            lookahead.next();
+            let max_digits = read_digits(&mut lookahead);
+            if max_digits.is_empty() || lookahead.peek().map(|(_, c)| *c) != Some('}') {
+                return None;
+            }
+            lookahead.next();
+            (min_digits.parse().ok()?, max_digits.parse().ok()?)
+        }
+        _ => return None,
+    };
+
+    if min > max {
+        return None;
+    }
+
+    *iter = lookahead;
+    Some((min, max))
+}
+
+/// Lower `group{min,max}` into a `Switch` whose alternatives repeat `group`
+/// `min..=max` times, so the generator still only ever walks literals and
+/// switches. Each extra repetition multiplies the candidate count by
+/// `group`'s own count, so e.g. `[0-9]{1,6}` alone already produces over a
+/// million candidates — callers should size quantifiers with that in mind.
+fn lower_repetition(group: Node, min: usize, max: usize) -> Node {
+    let options = (min..=max).map(|n| vec![group.clone(); n]).collect();
+    Node::Switch(options)
+}
+
+/// Parse a top-level sequence of literals and `{...}` switches, up to the end
+/// of `s`. A stray `}` at this level is always unmatched, since any `{` we
+/// open here is fully consumed (including its closing `}`) by [`parse_switch`].
+fn parse_seq(s: &str, iter: &mut Chars) -> Result<Vec<Node>> {
+    let mut nodes = Vec::new();
+    let mut x = String::new();
+
+    while let Some((i, c)) = iter.next() {
+        match c {
+            '{' => {
+                if !x.is_empty() {
+                    nodes.push(Node::Literal(std::mem::take(&mut x)));
                 }
+                let mut group = parse_switch(s, iter, i)?;
+                while let Some((min, max)) = try_parse_repeat(iter) {
+                    group = lower_repetition(group, min, max);
+                }
+                nodes.push(group);
             }
-            '\\' => {
-                escape = true;
+            '}' => {
+                return Err(
+                    Diagnostic::new(s, i..i + 1, "unmatched `}`, not in a switch statement").into(),
+                );
+            }
+            '[' => {
+                if !x.is_empty() {
+                    nodes.push(Node::Literal(std::mem::take(&mut x)));
+                }
+                let mut group = parse_class(s, iter, i)?;
+                while let Some((min, max)) = try_parse_repeat(iter) {
+                    group = lower_repetition(group, min, max);
+                }
+                nodes.push(group);
+            }
+            ']' => {
+                return Err(Diagnostic::new(
+                    s,
+                    i..i + 1,
+                    "unmatched `]`, not in a character class",
+                )
+                .into());
             }
+            '\\' => x.push(decode_escape(s, iter, i)?),
             c => x.push(c),
-        };
+        }
     }
 
     if !x.is_empty() {
-        tokens.push(Token::Chunk(x));
+        nodes.push(Node::Literal(x));
     }
 
-    if in_switch > 0 {
-        bail!("unmatched {{, still in switch at end of string");
+    Ok(nodes)
+}
+
+/// Parse the alternatives of a switch opened at `open_pos`, consuming up to
+/// and including its matching `}`. Alternatives are separated by `,`; an
+/// alternative that contains a `start..end` range is expanded into one
+/// alternative per value, as if the range had been written out as a
+/// comma-separated list.
+fn parse_switch(s: &str, iter: &mut Chars, open_pos: usize) -> Result<Node> {
+    let mut options: Vec<Vec<Node>> = Vec::new();
+    let mut current: Vec<Node> = Vec::new();
+    let mut x = String::new();
+    let mut chunk_start = open_pos + 1;
+
+    loop {
+        let (i, c) = match iter.next() {
+            Some(pair) => pair,
+            None => {
+                return Err(Diagnostic::new(
+                    s,
+                    open_pos..open_pos + 1,
+                    "unmatched `{`, still in switch at end of string",
+                )
+                .into());
+            }
+        };
+
+        match c {
+            '\\' => {
+                if x.is_empty() {
+                    chunk_start = i;
+                }
+                x.push(decode_escape(s, iter, i)?);
+            }
+            '{' => {
+                if !x.is_empty() {
+                    current.push(Node::Literal(std::mem::take(&mut x)));
+                }
+                let mut group = parse_switch(s, iter, i)?;
+                while let Some((min, max)) = try_parse_repeat(iter) {
+                    group = lower_repetition(group, min, max);
+                }
+                current.push(group);
+            }
+            '[' => {
+                if !x.is_empty() {
+                    current.push(Node::Literal(std::mem::take(&mut x)));
+                }
+                let mut group = parse_class(s, iter, i)?;
+                while let Some((min, max)) = try_parse_repeat(iter) {
+                    group = lower_repetition(group, min, max);
+                }
+                current.push(group);
+            }
+            ']' => {
+                return Err(Diagnostic::new(
+                    s,
+                    i..i + 1,
+                    "unmatched `]`, not in a character class",
+                )
+                .into());
+            }
+            '}' => {
+                if !x.is_empty() {
+                    current.push(Node::Literal(std::mem::take(&mut x)));
+                }
+                if !current.is_empty() {
+                    options.push(std::mem::take(&mut current));
+                }
+                return Ok(Node::Switch(options));
+            }
+            ',' => {
+                if !x.is_empty() {
+                    current.push(Node::Literal(std::mem::take(&mut x)));
+                }
+                options.push(std::mem::take(&mut current));
+
+                // an explicit trailing empty alternative, e.g. the `,}` in `{a,}`
+                if iter.peek().map(|(_, c)| *c) == Some('}') {
+                    options.push(Vec::new());
+                }
+            }
+            '.' if iter.peek().map(|(_, c)| *c) == Some('.') => {
+                iter.next(); // consume the second '.'
+
+                // read the end literal, stopping at `}` or at a second `..` (step)
+                let mut end = String::new();
+                loop {
+                    match iter.peek().copied() {
+                        Some((i, '\\')) => {
+                            iter.next();
+                            end.push(decode_escape(s, iter, i)?);
+                        }
+                        Some((_, '.')) => {
+                            let mut lookahead = iter.clone();
+                            lookahead.next();
+                            if lookahead.peek().map(|(_, c)| *c) == Some('.') {
+                                break;
+                            }
+                            iter.next();
+                            end.push('.');
+                        }
+                        Some((_, '}')) | None => break,
+                        Some((_, c)) => {
+                            iter.next();
+                            end.push(c);
+                        }
+                    }
+                }
+
+                // an optional `..step` segment
+                let step = if iter.peek().map(|(_, c)| *c) == Some('.') {
+                    iter.next();
+                    iter.next();
+
+                    let mut step = String::new();
+                    loop {
+                        match iter.peek().map(|(_, c)| *c) {
+                            Some('}') | None => break,
+                            Some(c) => {
+                                iter.next();
+                                step.push(c);
+                            }
+                        }
+                    }
+                    Some(step)
+                } else {
+                    None
+                };
+
+                let range_end = iter.peek().map(|(i, _)| *i).unwrap_or(s.len());
+                if iter.peek().map(|(_, c)| *c) != Some('}') {
+                    return Err(Diagnostic::new(
+                        s,
+                        chunk_start..range_end,
+                        "unterminated range, expected `}`",
+                    )
+                    .into());
+                }
+
+                let values = expand_range(s, chunk_start..range_end, &x, &end, step.as_deref())?;
+                x.clear();
+
+                // the first value shares this alternative's already-parsed
+                // prefix (usually empty); the rest each start a fresh one,
+                // mirroring a literal `start,val2,val3,...` comma list.
+                let mut values = values.into_iter();
+                if let Some(first) = values.next() {
+                    let mut first_alt = std::mem::take(&mut current);
+                    first_alt.push(Node::Literal(first));
+                    options.push(first_alt);
+                }
+                for value in values {
+                    options.push(vec![Node::Literal(value)]);
+                }
+            }
+            c => {
+                if x.is_empty() {
+                    chunk_start = i;
+                }
+                x.push(c);
+            }
+        }
     }
+}
 
-    Ok(tokens)
+pub fn parse(s: &str) -> Result<Vec<Node>> {
+    let mut iter = s.char_indices().peekable();
+    parse_seq(s, &mut iter)
 }
 
 #[cfg(test)]
@@ -142,7 +608,7 @@ mod test {
     #[test]
     fn simple() {
         let p = parse("abc").unwrap();
-        assert_eq!(p, vec![Token::Chunk(String::from("abc")),]);
+        assert_eq!(p, vec![Node::Literal(String::from("abc"))]);
     }
 
     #[test]
@@ -157,14 +623,12 @@ mod test {
         assert_eq!(
             p,
             vec![
-                Token::Chunk(String::from("abc")),
-                Token::SwitchOpen,
-                Token::Chunk(String::from("x")),
-                Token::SwitchNext,
-                Token::Chunk(String::from("y")),
-                Token::SwitchNext,
-                Token::Chunk(String::from("z")),
-                Token::SwitchClose,
+                Node::Literal(String::from("abc")),
+                Node::Switch(vec![
+                    vec![Node::Literal(String::from("x"))],
+                    vec![Node::Literal(String::from("y"))],
+                    vec![Node::Literal(String::from("z"))],
+                ]),
             ]
         );
     }
@@ -175,16 +639,14 @@ mod test {
         assert_eq!(
             p,
             vec![
-                Token::Chunk(String::from("abc")),
-                Token::SwitchOpen,
-                Token::Chunk(String::from("x")),
-                Token::SwitchNext,
-                Token::SwitchOpen,
-                Token::Chunk(String::from("y")),
-                Token::SwitchNext,
-                Token::Chunk(String::from("z")),
-                Token::SwitchClose,
-                Token::SwitchClose,
+                Node::Literal(String::from("abc")),
+                Node::Switch(vec![
+                    vec![Node::Literal(String::from("x"))],
+                    vec![Node::Switch(vec![
+                        vec![Node::Literal(String::from("y"))],
+                        vec![Node::Literal(String::from("z"))],
+                    ])],
+                ]),
             ]
         );
     }
@@ -195,15 +657,14 @@ mod test {
         assert_eq!(
             p,
             vec![
-                Token::Chunk(String::from("abc")),
-                Token::SwitchOpen,
-                Token::Chunk(String::from("x")),
-                Token::SwitchOpen,
-                Token::Chunk(String::from("y")),
-                Token::SwitchNext,
-                Token::Chunk(String::from("z")),
-                Token::SwitchClose,
-                Token::SwitchClose,
+                Node::Literal(String::from("abc")),
+                Node::Switch(vec![vec![
+                    Node::Literal(String::from("x")),
+                    Node::Switch(vec![
+                        vec![Node::Literal(String::from("y"))],
+                        vec![Node::Literal(String::from("z"))],
+                    ]),
+                ]]),
             ]
         );
     }
@@ -214,16 +675,14 @@ mod test {
         assert_eq!(
             p,
             vec![
-                Token::SwitchOpen,
-                Token::SwitchOpen,
-                Token::Chunk(String::from("a")),
-                Token::SwitchNext,
-                Token::Chunk(String::from("b")),
-                Token::SwitchClose,
-                Token::SwitchNext,
-                Token::Chunk(String::from("")),
-                Token::SwitchClose,
-                Token::Chunk(String::from("x")),
+                Node::Switch(vec![
+                    vec![Node::Switch(vec![
+                        vec![Node::Literal(String::from("a"))],
+                        vec![Node::Literal(String::from("b"))],
+                    ])],
+                    vec![],
+                ]),
+                Node::Literal(String::from("x")),
             ]
         );
     }
@@ -234,18 +693,15 @@ mod test {
         assert_eq!(
             p,
             vec![
-                Token::SwitchOpen,
-                Token::SwitchOpen,
-                Token::Chunk(String::from("a")),
-                Token::SwitchNext,
-                Token::Chunk(String::from("b")),
-                Token::SwitchClose,
-                Token::SwitchNext,
-                Token::Chunk(String::from("")),
-                Token::SwitchNext,
-                Token::Chunk(String::from("")),
-                Token::SwitchClose,
-                Token::Chunk(String::from("x")),
+                Node::Switch(vec![
+                    vec![Node::Switch(vec![
+                        vec![Node::Literal(String::from("a"))],
+                        vec![Node::Literal(String::from("b"))],
+                    ])],
+                    vec![],
+                    vec![],
+                ]),
+                Node::Literal(String::from("x")),
             ]
         );
     }
@@ -256,14 +712,12 @@ mod test {
         assert_eq!(
             p,
             vec![
-                Token::Chunk(String::from("abc")),
-                Token::SwitchOpen,
-                Token::Chunk(String::from("x")),
-                Token::SwitchNext,
-                Token::Chunk(String::from("y")),
-                Token::SwitchNext,
-                Token::Chunk(String::from("")),
-                Token::SwitchClose,
+                Node::Literal(String::from("abc")),
+                Node::Switch(vec![
+                    vec![Node::Literal(String::from("x"))],
+                    vec![Node::Literal(String::from("y"))],
+                    vec![],
+                ]),
             ]
         );
     }
@@ -274,14 +728,12 @@ mod test {
         assert_eq!(
             p,
             vec![
-                Token::Chunk(String::from("abc")),
-                Token::SwitchOpen,
-                Token::Chunk(String::from("")),
-                Token::SwitchNext,
-                Token::Chunk(String::from("x")),
-                Token::SwitchNext,
-                Token::Chunk(String::from("y")),
-                Token::SwitchClose,
+                Node::Literal(String::from("abc")),
+                Node::Switch(vec![
+                    vec![],
+                    vec![Node::Literal(String::from("x"))],
+                    vec![Node::Literal(String::from("y"))],
+                ]),
             ]
         );
     }
@@ -292,46 +744,323 @@ mod test {
         assert_eq!(
             p,
             vec![
-                Token::Chunk(String::from("abc")),
-                Token::SwitchOpen,
-                Token::Chunk(String::from("x")),
-                Token::SwitchNext,
-                Token::Chunk(String::from("")),
-                Token::SwitchNext,
-                Token::Chunk(String::from("y")),
-                Token::SwitchClose,
+                Node::Literal(String::from("abc")),
+                Node::Switch(vec![
+                    vec![Node::Literal(String::from("x"))],
+                    vec![],
+                    vec![Node::Literal(String::from("y"))],
+                ]),
             ]
         );
     }
 
+    #[test]
+    fn empty_switch() {
+        let p = parse("{}").unwrap();
+        assert_eq!(p, vec![Node::Switch(vec![])]);
+    }
+
     #[test]
     fn numeric_range() {
         let p = parse("{0..9}").unwrap();
         assert_eq!(
             p,
-            vec![
-                Token::SwitchOpen,
-                Token::Chunk(String::from("0")),
-                Token::SwitchNext,
-                Token::Chunk(String::from("1")),
-                Token::SwitchNext,
-                Token::Chunk(String::from("2")),
-                Token::SwitchNext,
-                Token::Chunk(String::from("3")),
-                Token::SwitchNext,
-                Token::Chunk(String::from("4")),
-                Token::SwitchNext,
-                Token::Chunk(String::from("5")),
-                Token::SwitchNext,
-                Token::Chunk(String::from("6")),
-                Token::SwitchNext,
-                Token::Chunk(String::from("7")),
-                Token::SwitchNext,
-                Token::Chunk(String::from("8")),
-                Token::SwitchNext,
-                Token::Chunk(String::from("9")),
-                Token::SwitchClose,
-            ]
+            vec![Node::Switch(
+                (0..=9)
+                    .map(|n| vec![Node::Literal(n.to_string())])
+                    .collect()
+            )]
+        );
+    }
+
+    #[test]
+    fn alphabetic_range() {
+        let p = parse("{a..c}").unwrap();
+        assert_eq!(
+            p,
+            vec![Node::Switch(vec![
+                vec![Node::Literal(String::from("a"))],
+                vec![Node::Literal(String::from("b"))],
+                vec![Node::Literal(String::from("c"))],
+            ])]
+        );
+    }
+
+    #[test]
+    fn multi_digit_range() {
+        let p = parse("{8..11}").unwrap();
+        assert_eq!(
+            p,
+            vec![Node::Switch(vec![
+                vec![Node::Literal(String::from("8"))],
+                vec![Node::Literal(String::from("9"))],
+                vec![Node::Literal(String::from("10"))],
+                vec![Node::Literal(String::from("11"))],
+            ])]
+        );
+    }
+
+    #[test]
+    fn descending_range() {
+        let p = parse("{2..0}").unwrap();
+        assert_eq!(
+            p,
+            vec![Node::Switch(vec![
+                vec![Node::Literal(String::from("2"))],
+                vec![Node::Literal(String::from("1"))],
+                vec![Node::Literal(String::from("0"))],
+            ])]
+        );
+    }
+
+    #[test]
+    fn stepped_range() {
+        let p = parse("{0..10..5}").unwrap();
+        assert_eq!(
+            p,
+            vec![Node::Switch(vec![
+                vec![Node::Literal(String::from("0"))],
+                vec![Node::Literal(String::from("5"))],
+                vec![Node::Literal(String::from("10"))],
+            ])]
+        );
+    }
+
+    #[test]
+    fn zero_padded_range() {
+        let p = parse("{01..03}").unwrap();
+        assert_eq!(
+            p,
+            vec![Node::Switch(vec![
+                vec![Node::Literal(String::from("01"))],
+                vec![Node::Literal(String::from("02"))],
+                vec![Node::Literal(String::from("03"))],
+            ])]
+        );
+    }
+
+    #[test]
+    fn range_step_zero_errs() {
+        assert!(parse("{0..10..0}").is_err());
+    }
+
+    #[test]
+    fn oversized_range_errs() {
+        assert!(parse("{0..999999999}").is_err());
+    }
+
+    #[test]
+    fn mixed_case_alphabetic_range_errs() {
+        assert!(parse("{a..Z}").is_err());
+        assert!(parse("{A..z}").is_err());
+    }
+
+    #[test]
+    fn unmatched_open_brace_errs() {
+        assert!(parse("abc{x,y").is_err());
+    }
+
+    #[test]
+    fn unmatched_close_brace_errs() {
+        assert!(parse("abc}").is_err());
+    }
+
+    #[test]
+    fn character_class() {
+        let p = parse("[xy]").unwrap();
+        assert_eq!(
+            p,
+            vec![Node::Switch(vec![
+                vec![Node::Literal(String::from("x"))],
+                vec![Node::Literal(String::from("y"))],
+            ])]
+        );
+    }
+
+    #[test]
+    fn character_class_range() {
+        let p = parse("[a-c]").unwrap();
+        assert_eq!(
+            p,
+            vec![Node::Switch(vec![
+                vec![Node::Literal(String::from("a"))],
+                vec![Node::Literal(String::from("b"))],
+                vec![Node::Literal(String::from("c"))],
+            ])]
+        );
+    }
+
+    #[test]
+    fn character_class_mixed() {
+        let p = parse("[a-c_]").unwrap();
+        assert_eq!(
+            p,
+            vec![Node::Switch(vec![
+                vec![Node::Literal(String::from("a"))],
+                vec![Node::Literal(String::from("b"))],
+                vec![Node::Literal(String::from("c"))],
+                vec![Node::Literal(String::from("_"))],
+            ])]
+        );
+    }
+
+    #[test]
+    fn character_class_trailing_dash_is_literal() {
+        let p = parse("[a-]").unwrap();
+        assert_eq!(
+            p,
+            vec![Node::Switch(vec![
+                vec![Node::Literal(String::from("a"))],
+                vec![Node::Literal(String::from("-"))],
+            ])]
+        );
+    }
+
+    #[test]
+    fn character_class_descending_range_errs() {
+        assert!(parse("[z-a]").is_err());
+    }
+
+    #[test]
+    fn unmatched_open_bracket_errs() {
+        assert!(parse("[abc").is_err());
+    }
+
+    #[test]
+    fn unmatched_close_bracket_errs() {
+        assert!(parse("abc]").is_err());
+    }
+
+    #[test]
+    fn bounded_repetition() {
+        let p = parse("{a,b}{2,3}").unwrap();
+        let ab = || {
+            Node::Switch(vec![
+                vec![Node::Literal(String::from("a"))],
+                vec![Node::Literal(String::from("b"))],
+            ])
+        };
+        assert_eq!(
+            p,
+            vec![Node::Switch(vec![vec![ab(), ab()], vec![ab(), ab(), ab()]])]
+        );
+    }
+
+    #[test]
+    fn exact_repetition() {
+        let p = parse("{a,b}{2}").unwrap();
+        let ab = Node::Switch(vec![
+            vec![Node::Literal(String::from("a"))],
+            vec![Node::Literal(String::from("b"))],
+        ]);
+        assert_eq!(p, vec![Node::Switch(vec![vec![ab.clone(), ab]])]);
+    }
+
+    #[test]
+    fn repetition_on_character_class() {
+        let p = parse("[ab]{1,2}").unwrap();
+        let ab = || {
+            Node::Switch(vec![
+                vec![Node::Literal(String::from("a"))],
+                vec![Node::Literal(String::from("b"))],
+            ])
+        };
+        assert_eq!(p, vec![Node::Switch(vec![vec![ab()], vec![ab(), ab()]])]);
+    }
+
+    #[test]
+    fn non_quantifier_switch_stays_independent() {
+        // `{x,y,z}{x,y,z}` is two independent switches, not a quantifier,
+        // since its content isn't a bare `{m}`/`{m,n}` digit form.
+        let p = parse("{x,y,z}{x,y,z}").unwrap();
+        match p.as_slice() {
+            [Node::Switch(_), Node::Switch(_)] => {}
+            other => panic!("expected two independent switches, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn named_escapes() {
+        let p = parse(r"\n\r\t\0").unwrap();
+        assert_eq!(p, vec![Node::Literal(String::from("\n\r\t\0"))]);
+    }
+
+    #[test]
+    fn escaped_metacharacters_stay_literal() {
+        let p = parse(r"\{\}\,\.").unwrap();
+        assert_eq!(p, vec![Node::Literal(String::from("{},."))]);
+    }
+
+    #[test]
+    fn hex_byte_escape() {
+        let p = parse(r"\x41\x2a").unwrap();
+        assert_eq!(p, vec![Node::Literal(String::from("A*"))]);
+    }
+
+    #[test]
+    fn hex_byte_escape_above_ascii_expands_to_utf8() {
+        // `\xFF` doesn't round-trip to the single raw byte 0xFF: `String`
+        // must be valid UTF-8, so it comes out as U+00FF encoded as the two
+        // bytes 0xC3 0xBF instead. Pinning this down so a future change
+        // doesn't accidentally "fix" it without updating the documented
+        // caveat on `decode_escape`.
+        let p = parse(r"\xFF").unwrap();
+        assert_eq!(p, vec![Node::Literal(String::from("\u{ff}"))]);
+        assert_eq!("\u{ff}".as_bytes(), [0xC3, 0xBF]);
+    }
+
+    #[test]
+    fn hex_byte_escape_rejects_non_hex() {
+        assert!(parse(r"\xzz").is_err());
+    }
+
+    #[test]
+    fn hex_byte_escape_rejects_single_digit() {
+        assert!(parse(r"\x4").is_err());
+    }
+
+    #[test]
+    fn unicode_escape() {
+        let p = parse(r"\u{1F600}").unwrap();
+        assert_eq!(p, vec![Node::Literal(String::from("\u{1F600}"))]);
+    }
+
+    #[test]
+    fn unicode_escape_requires_brace() {
+        assert!(parse(r"\u41").is_err());
+    }
+
+    #[test]
+    fn unicode_escape_rejects_unterminated() {
+        assert!(parse(r"\u{41").is_err());
+    }
+
+    #[test]
+    fn unicode_escape_rejects_out_of_range() {
+        assert!(parse(r"\u{110000}").is_err());
+    }
+
+    #[test]
+    fn escapes_work_inside_switch() {
+        let p = parse(r"{\x41,\n}").unwrap();
+        assert_eq!(
+            p,
+            vec![Node::Switch(vec![
+                vec![Node::Literal(String::from("A"))],
+                vec![Node::Literal(String::from("\n"))],
+            ])]
+        );
+    }
+
+    #[test]
+    fn escapes_work_inside_character_class() {
+        let p = parse(r"[\n\x41]").unwrap();
+        assert_eq!(
+            p,
+            vec![Node::Switch(vec![
+                vec![Node::Literal(String::from("\n"))],
+                vec![Node::Literal(String::from("A"))],
+            ])]
         );
     }
 }